@@ -0,0 +1,110 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::index::Index;
+use crate::snapshot;
+
+/// Length of a hex-encoded SHA-512 digest, used to recognize blob
+/// files (`<hash>.<ext>`) among everything else in the clippings dir.
+const HASH_LEN: usize = 128;
+
+pub(crate) struct GcReport {
+    pub(crate) reclaimable_bytes: u64,
+    pub(crate) removed: Vec<PathBuf>,
+}
+
+/// Scans `clippings_dir` for blobs that aren't referenced by the
+/// index or by any manifest stored alongside it. In dry-run mode
+/// (the default) it only reports what could be reclaimed; otherwise
+/// it deletes the unreferenced blobs.
+pub(crate) fn run(clippings_dir: &Path, dry_run: bool) -> Result<GcReport> {
+    let index = Index::load(clippings_dir)?;
+    if index.entries().next().is_none() && index.manifests().is_empty() {
+        log::warn!(
+            "index at {:?} has no entries or manifests; blobs added before indexing began \
+             (e.g. by an index-unaware version of `store`) will look unreferenced and may be \
+             deleted by `gc --apply`",
+            clippings_dir
+        );
+    }
+
+    let referenced = referenced_hashes(clippings_dir, &index)?;
+
+    let mut reclaimable_bytes = 0;
+    let mut removed = Vec::new();
+    for entry in
+        std::fs::read_dir(clippings_dir).with_context(|| format!("listing {:?}", clippings_dir))?
+    {
+        let path = entry?.path();
+        let hash = match blob_hash(&path) {
+            Some(hash) => hash,
+            None => continue,
+        };
+        if referenced.contains(&hash) {
+            continue;
+        }
+
+        let size = path.metadata().context("reading file metadata")?.len();
+        reclaimable_bytes += size;
+        if dry_run {
+            log::info!("would remove unreferenced blob {:?} ({} bytes)", path, size);
+        } else {
+            std::fs::remove_file(&path).with_context(|| format!("removing {:?}", path))?;
+            log::info!("removed unreferenced blob {:?} ({} bytes)", path, size);
+        }
+        removed.push(path);
+    }
+
+    Ok(GcReport {
+        reclaimable_bytes,
+        removed,
+    })
+}
+
+fn referenced_hashes(clippings_dir: &Path, index: &Index) -> Result<HashSet<String>> {
+    let mut referenced: HashSet<String> = index.entries().map(|e| e.hash.clone()).collect();
+
+    // Manifests `store dir` registered, wherever they were saved
+    // (including outside `clippings_dir` via `--manifest`).
+    let mut manifest_paths: Vec<PathBuf> = index.manifests().to_vec();
+
+    // Also pick up manifests saved at their default location, in case
+    // they predate manifest registration in the index.
+    for entry in
+        std::fs::read_dir(clippings_dir).with_context(|| format!("listing {:?}", clippings_dir))?
+    {
+        let path = entry?.path();
+        if path.to_string_lossy().ends_with(".manifest.json") && !manifest_paths.contains(&path) {
+            manifest_paths.push(path);
+        }
+    }
+
+    for manifest_path in manifest_paths {
+        match snapshot::load_manifest(&manifest_path) {
+            Ok(manifest) => referenced.extend(manifest.entries.into_iter().map(|e| e.hash)),
+            Err(e) => log::warn!(
+                "could not read manifest {:?}, treating blobs it might reference as unreferenced: {:?}",
+                manifest_path,
+                e
+            ),
+        }
+    }
+
+    Ok(referenced)
+}
+
+/// Returns the content hash a clippings-dir entry stores under, or
+/// `None` if the entry isn't a `<hash>.<ext>` blob (e.g. `index.json`
+/// or a manifest).
+fn blob_hash(path: &Path) -> Option<String> {
+    if !path.is_file() {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    if stem.len() == HASH_LEN && stem.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(stem.to_owned())
+    } else {
+        None
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Everything we know about a single stored item, keyed by its content
+/// hash in the [`Index`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct IndexEntry {
+    pub(crate) hash: String,
+    pub(crate) original_name: String,
+    pub(crate) source_path: PathBuf,
+    pub(crate) added_at: u64,
+    pub(crate) size: u64,
+    pub(crate) extension: String,
+    /// Perceptual hash (dHash) for image entries, used to catch
+    /// near-identical screenshots that differ in content hash.
+    #[serde(default)]
+    pub(crate) phash: Option<u64>,
+}
+
+/// Sidecar index of everything stored in the clippings directory,
+/// persisted as `index.json` next to the blobs themselves.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Index {
+    entries: HashMap<String, IndexEntry>,
+    /// Paths of manifests written by `store dir`, including ones
+    /// saved outside the clippings directory via `--manifest`. `store
+    /// gc` reads these so it doesn't reclaim blobs a manifest still
+    /// references.
+    #[serde(default)]
+    manifests: Vec<PathBuf>,
+}
+
+impl Index {
+    const FILE_NAME: &'static str = "index.json";
+
+    fn path(clippings_dir: &Path) -> PathBuf {
+        clippings_dir.join(Self::FILE_NAME)
+    }
+
+    pub(crate) fn load(clippings_dir: &Path) -> Result<Self> {
+        let path = Self::path(clippings_dir);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path).context("reading index")?;
+        serde_json::from_str(&contents).context("parsing index")
+    }
+
+    /// Writes the index out atomically: write to a temp file, then
+    /// rename over the real path, so a crash mid-write can't corrupt it.
+    pub(crate) fn save(&self, clippings_dir: &Path) -> Result<()> {
+        let path = Self::path(clippings_dir);
+        let tmp_path = path.with_extension("json.tmp");
+        let contents = serde_json::to_string_pretty(self).context("serializing index")?;
+        std::fs::write(&tmp_path, contents).context("writing index")?;
+        std::fs::rename(&tmp_path, &path).context("persisting index")?;
+        Ok(())
+    }
+
+    pub(crate) fn contains(&self, hash: &str) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    pub(crate) fn insert(&mut self, entry: IndexEntry) {
+        self.entries.insert(entry.hash.clone(), entry);
+    }
+
+    pub(crate) fn get(&self, hash: &str) -> Option<&IndexEntry> {
+        self.entries.get(hash)
+    }
+
+    pub(crate) fn entries(&self) -> impl Iterator<Item = &IndexEntry> {
+        self.entries.values()
+    }
+
+    pub(crate) fn find_by_name(&self, name: &str) -> Option<&IndexEntry> {
+        self.entries.values().find(|e| e.original_name == name)
+    }
+
+    pub(crate) fn add_manifest(&mut self, path: PathBuf) {
+        if !self.manifests.contains(&path) {
+            self.manifests.push(path);
+        }
+    }
+
+    pub(crate) fn manifests(&self) -> &[PathBuf] {
+        &self.manifests
+    }
+}
+
+pub(crate) fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(hash: &str, original_name: &str) -> IndexEntry {
+        IndexEntry {
+            hash: hash.to_owned(),
+            original_name: original_name.to_owned(),
+            source_path: PathBuf::from("/tmp/source"),
+            added_at: 0,
+            size: 0,
+            extension: String::new(),
+            phash: None,
+        }
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = std::env::temp_dir().join(format!("store-things-index-test-{}", now_unix()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut idx = Index::default();
+        idx.insert(entry("abc", "screenshot.png"));
+        idx.add_manifest(PathBuf::from("/elsewhere/project.manifest.json"));
+        idx.save(&dir).unwrap();
+
+        let loaded = Index::load(&dir).unwrap();
+        assert!(loaded.contains("abc"));
+        assert_eq!(loaded.find_by_name("screenshot.png").unwrap().hash, "abc");
+        assert_eq!(
+            loaded.manifests(),
+            &[PathBuf::from("/elsewhere/project.manifest.json")]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn add_manifest_dedups() {
+        let mut idx = Index::default();
+        idx.add_manifest(PathBuf::from("/a.json"));
+        idx.add_manifest(PathBuf::from("/a.json"));
+        assert_eq!(idx.manifests().len(), 1);
+    }
+}
@@ -6,11 +6,22 @@ use std::io::{prelude::*, BufReader};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+mod gc;
+mod index;
+mod phash;
+mod snapshot;
+mod watch;
+
+use index::{Index, IndexEntry};
+
 #[derive(serde::Deserialize)]
-struct Config {
+pub(crate) struct Config {
     clippings: PathBuf,
     strip_dir: Option<PathBuf>,
     screenshot_dir: PathBuf,
+    phash_threshold: Option<u32>,
+    allowed_extensions: Option<Vec<String>>,
+    excluded_extensions: Option<Vec<String>>,
 }
 
 impl Config {
@@ -29,14 +40,36 @@ impl Config {
         }
     }
 
-    fn get_clippings_dir(&self) -> Result<PathBuf> {
+    pub(crate) fn get_clippings_dir(&self) -> Result<PathBuf> {
         expand_user(&self.clippings)
     }
 
-    fn get_screenshot_dir(&self) -> Result<PathBuf> {
+    pub(crate) fn get_screenshot_dir(&self) -> Result<PathBuf> {
         expand_user(&self.screenshot_dir)
     }
 
+    /// Maximum dHash Hamming distance for two images to be considered
+    /// near-duplicates, defaulting to 5 bits out of 64.
+    pub(crate) fn phash_threshold(&self) -> u32 {
+        self.phash_threshold.unwrap_or(5)
+    }
+
+    /// Whether `extension` passes the configured allow/deny lists,
+    /// compared case-insensitively. With neither `excluded_extensions`
+    /// nor `allowed_extensions` configured, every extension (including
+    /// an empty one) is allowed.
+    pub(crate) fn extension_allowed(&self, extension: &str) -> bool {
+        if let Some(excluded) = &self.excluded_extensions {
+            if excluded.iter().any(|e| e.eq_ignore_ascii_case(extension)) {
+                return false;
+            }
+        }
+        if let Some(allowed) = &self.allowed_extensions {
+            return allowed.iter().any(|e| e.eq_ignore_ascii_case(extension));
+        }
+        true
+    }
+
     fn strip_prefix(&self, path: &PathBuf) -> Result<PathBuf> {
         if let Some(prefix) = self.strip_dir.as_ref() {
             let prefix = expand_user(prefix)?;
@@ -54,12 +87,12 @@ impl Config {
     }
 }
 
-fn most_recent_file(dir: &PathBuf) -> Result<PathBuf> {
+fn most_recent_file(config: &Config, dir: &PathBuf) -> Result<PathBuf> {
     let dir = expand_user(dir)?;
     let mut files = Vec::new();
     for entry in std::fs::read_dir(&dir).context("listing directory")? {
         let path = entry?.path();
-        if path.is_file() {
+        if path.is_file() && config.extension_allowed(&file_extension(&path)) {
             files.push(path);
         }
     }
@@ -67,6 +100,12 @@ fn most_recent_file(dir: &PathBuf) -> Result<PathBuf> {
     files.last().cloned().context("getting last file")
 }
 
+fn file_extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
 fn expand_user(path: &PathBuf) -> Result<PathBuf> {
     let home = std::env::home_dir().context("getting home dir")?;
     let path = path.to_str().context("conversion to string")?;
@@ -99,28 +138,104 @@ fn hash_contents<P: AsRef<Path>>(path: P) -> Result<String> {
     inner(path.as_ref())
 }
 
-fn do_add<P: AsRef<Path>>(config: &Config, path: P) -> Result<PathBuf> {
-    let hash = hash_contents(&path)?;
+/// Resolves the clippings directory (creating it if needed) and the
+/// target path a blob with this hash/extension would live at.
+fn prepare_target(config: &Config, hash: &str, extension: &str) -> Result<(PathBuf, PathBuf)> {
+    let clippings_dir = config.get_clippings_dir()?;
+    if !clippings_dir.is_dir() {
+        std::fs::create_dir(&clippings_dir).context("creating clippings directory")?;
+    }
+    let mut target = clippings_dir.join(hash);
+    target.set_extension(extension);
+    Ok((clippings_dir, target))
+}
 
+/// Copies `path` into the content store by its exact content hash,
+/// recording it in the index. Unlike [`store_blob`], this never
+/// collapses perceptually-similar images into one entry, so it's the
+/// right primitive for directory snapshotting, where every file must
+/// be preserved verbatim for the manifest to restore correctly.
+pub(crate) fn store_blob_exact<P: AsRef<Path>>(config: &Config, path: P) -> Result<PathBuf> {
+    let hash = hash_contents(&path)?;
     let extension = path
         .as_ref()
         .extension()
         .map(|ext| ext.to_str().unwrap())
         .unwrap_or("");
 
-    let clippings_dir = config.get_clippings_dir()?;
-    if !clippings_dir.is_dir() {
-        std::fs::create_dir(&clippings_dir).context("creating clippings directory")?;
+    let (clippings_dir, target) = prepare_target(config, &hash, extension)?;
+
+    if target.is_file() {
+        log::info!("File {:?} already exists, skipping", target);
+    } else {
+        std::fs::copy(&path, &target)?;
     }
-    let mut target = clippings_dir.join(hash);
-    target.set_extension(extension);
+
+    let phash = phash::dhash(path.as_ref());
+    record_in_index(&clippings_dir, &hash, path.as_ref(), extension, phash)?;
+
+    Ok(target)
+}
+
+/// Copies `path` into the content store, deduplicating by exact hash
+/// and, for images, by perceptual near-duplicate against the index.
+/// Used by interactive ingestion ([`do_add`]); snapshotting uses
+/// [`store_blob_exact`] instead, since it must preserve every file.
+pub(crate) fn store_blob<P: AsRef<Path>>(config: &Config, path: P) -> Result<PathBuf> {
+    let hash = hash_contents(&path)?;
+    let extension = path
+        .as_ref()
+        .extension()
+        .map(|ext| ext.to_str().unwrap())
+        .unwrap_or("");
+
+    let (clippings_dir, target) = prepare_target(config, &hash, extension)?;
+
+    let phash = phash::dhash(path.as_ref());
 
     if target.is_file() {
         log::info!("File {:?} already exists, skipping", target);
+    } else if let Some(existing) =
+        phash.and_then(|h| find_near_duplicate(&clippings_dir, h, config.phash_threshold()))
+    {
+        log::warn!(
+            "{:?} looks like a near-duplicate of {:?}, reusing it instead of copying",
+            path.as_ref(),
+            existing
+        );
+        return Ok(existing);
     } else {
         std::fs::copy(&path, &target)?;
     }
 
+    record_in_index(&clippings_dir, &hash, path.as_ref(), extension, phash)?;
+
+    Ok(target)
+}
+
+/// Looks for an existing index entry whose perceptual hash is within
+/// `threshold` Hamming distance of `phash`, returning its stored path.
+fn find_near_duplicate(clippings_dir: &Path, phash: u64, threshold: u32) -> Option<PathBuf> {
+    let idx = Index::load(clippings_dir).ok()?;
+    idx.entries()
+        .filter_map(|e| e.phash.map(|h| (e, phash::hamming_distance(h, phash))))
+        .filter(|(_, distance)| *distance <= threshold)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(e, _)| clippings_dir.join(&e.hash).with_extension(&e.extension))
+}
+
+pub(crate) fn do_add<P: AsRef<Path>>(config: &Config, path: P) -> Result<PathBuf> {
+    let extension = file_extension(path.as_ref());
+    if !config.extension_allowed(&extension) {
+        bail!(
+            "extension `{}` of {:?} is not allowed by the configured filters",
+            extension,
+            path.as_ref()
+        );
+    }
+
+    let target = store_blob(config, &path)?;
+
     std::process::Command::new("wl-copy")
         .arg(&config.strip_prefix(&target)?)
         .spawn()?
@@ -129,6 +244,130 @@ fn do_add<P: AsRef<Path>>(config: &Config, path: P) -> Result<PathBuf> {
     Ok(target)
 }
 
+/// Records a newly-seen hash in the index, if it isn't already there.
+/// Existing entries are left untouched: the content-addressed blob
+/// doesn't change, so the first-seen metadata stays authoritative.
+fn record_in_index(
+    clippings_dir: &Path,
+    hash: &str,
+    source: &Path,
+    extension: &str,
+    phash: Option<u64>,
+) -> Result<()> {
+    let mut idx = Index::load(clippings_dir)?;
+    if !idx.contains(hash) {
+        let original_name = source
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let source_path = std::fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf());
+        let size = source.metadata().context("reading file metadata")?.len();
+        idx.insert(IndexEntry {
+            hash: hash.to_owned(),
+            original_name,
+            source_path,
+            added_at: index::now_unix(),
+            size,
+            extension: extension.to_owned(),
+            phash,
+        });
+        idx.save(clippings_dir)?;
+    }
+    Ok(())
+}
+
+fn cmd_list(config: &Config, filter: Option<&str>) -> Result<()> {
+    let clippings_dir = config.get_clippings_dir()?;
+    let idx = Index::load(&clippings_dir)?;
+    let mut entries: Vec<&IndexEntry> = idx.entries().collect();
+    entries.sort_by_key(|e| e.added_at);
+    for entry in entries {
+        if let Some(filter) = filter {
+            if !entry.original_name.contains(filter) {
+                continue;
+            }
+        }
+        println!(
+            "{hash}  {name}  {size} bytes  {path:?}",
+            hash = entry.hash,
+            name = entry.original_name,
+            size = entry.size,
+            path = clippings_dir.join(&entry.hash).with_extension(&entry.extension),
+        );
+    }
+    Ok(())
+}
+
+/// Resolves `name` to a stored item, trying it first as an original
+/// file name and falling back to treating it as a content hash
+/// directly (e.g. one printed by `store list`).
+fn cmd_find(config: &Config, name: &str) -> Result<()> {
+    let clippings_dir = config.get_clippings_dir()?;
+    let idx = Index::load(&clippings_dir)?;
+    let entry = idx
+        .find_by_name(name)
+        .or_else(|| idx.get(name))
+        .with_context(|| format!("no stored item named {:?}", name))?;
+    let path = clippings_dir.join(&entry.hash).with_extension(&entry.extension);
+    println!("{}", path.display());
+    Ok(())
+}
+
+fn cmd_dir(config: &Config, dir: &Path, manifest_path: Option<&str>) -> Result<()> {
+    let manifest = snapshot::snapshot(config, dir)?;
+    let manifest_path = match manifest_path {
+        Some(p) => PathBuf::from(p),
+        None => {
+            let name = dir
+                .file_name()
+                .context("determining snapshot name")?
+                .to_string_lossy()
+                .into_owned();
+            config.get_clippings_dir()?.join(format!("{}.manifest.json", name))
+        }
+    };
+    snapshot::save_manifest(&manifest, &manifest_path)?;
+    register_manifest(&config.get_clippings_dir()?, &manifest_path)?;
+    println!("{}", manifest_path.display());
+    Ok(())
+}
+
+/// Records that a manifest now references blobs in the store, so
+/// `store gc` can find it even when it was saved outside the
+/// clippings directory.
+fn register_manifest(clippings_dir: &Path, manifest_path: &Path) -> Result<()> {
+    let absolute =
+        std::fs::canonicalize(manifest_path).unwrap_or_else(|_| manifest_path.to_path_buf());
+    let mut idx = Index::load(clippings_dir)?;
+    idx.add_manifest(absolute);
+    idx.save(clippings_dir)?;
+    Ok(())
+}
+
+fn cmd_restore(config: &Config, manifest_path: &Path, dest: &Path) -> Result<()> {
+    let manifest = snapshot::load_manifest(manifest_path)?;
+    snapshot::restore(config, &manifest, dest)
+}
+
+fn cmd_gc(config: &Config, apply: bool) -> Result<()> {
+    let clippings_dir = config.get_clippings_dir()?;
+    let report = gc::run(&clippings_dir, !apply)?;
+    if apply {
+        println!(
+            "removed {} blobs, reclaimed {} bytes",
+            report.removed.len(),
+            report.reclaimable_bytes
+        );
+    } else {
+        println!(
+            "{} reclaimable blobs, {} bytes (dry run, pass --apply to delete)",
+            report.removed.len(),
+            report.reclaimable_bytes
+        );
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
     env_logger::init();
 
@@ -139,13 +378,79 @@ fn main() -> Result<()> {
                 .long("last-screenshot")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .help("watch the screenshot directory and add new files automatically")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(Arg::new("path").required(false))
+        .subcommand(
+            Command::new("list")
+                .about("list stored items")
+                .arg(Arg::new("filter").long("filter").required(false)),
+        )
+        .subcommand(
+            Command::new("find")
+                .about("resolve an original file name to its stored path")
+                .arg(Arg::new("name").required(true)),
+        )
+        .subcommand(
+            Command::new("dir")
+                .about("snapshot a directory tree into the content store")
+                .arg(Arg::new("path").required(true))
+                .arg(Arg::new("manifest").long("manifest").required(false)),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("rebuild a directory tree from a manifest")
+                .arg(Arg::new("manifest").required(true))
+                .arg(Arg::new("dest").required(true)),
+        )
+        .subcommand(
+            Command::new("gc")
+                .about("reclaim space used by blobs no index entry or manifest references")
+                .arg(
+                    Arg::new("apply")
+                        .long("apply")
+                        .help("actually delete unreferenced blobs instead of just reporting them")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
         .get_matches();
 
     let config = Config::get()?;
+
+    match args.subcommand() {
+        Some(("list", sub)) => {
+            return cmd_list(&config, sub.get_one::<String>("filter").map(String::as_str));
+        }
+        Some(("find", sub)) => {
+            return cmd_find(&config, sub.get_one::<String>("name").unwrap());
+        }
+        Some(("dir", sub)) => {
+            let path = Path::new(sub.get_one::<String>("path").unwrap());
+            let manifest = sub.get_one::<String>("manifest").map(String::as_str);
+            return cmd_dir(&config, path, manifest);
+        }
+        Some(("restore", sub)) => {
+            let manifest = Path::new(sub.get_one::<String>("manifest").unwrap());
+            let dest = Path::new(sub.get_one::<String>("dest").unwrap());
+            return cmd_restore(&config, manifest, dest);
+        }
+        Some(("gc", sub)) => {
+            return cmd_gc(&config, sub.get_flag("apply"));
+        }
+        _ => {}
+    }
+
+    if args.get_flag("watch") {
+        return watch::run(&config);
+    }
+
     let path: String = if args.get_flag("last-screenshot") {
         let screen_dir = config.get_screenshot_dir()?;
-        most_recent_file(&screen_dir)?
+        most_recent_file(&config, &screen_dir)?
             .to_str()
             .context("converting to string")?
             .to_owned()
@@ -158,3 +463,72 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn test_config(clippings: PathBuf) -> Config {
+        Config {
+            clippings,
+            strip_dir: None,
+            screenshot_dir: PathBuf::new(),
+            phash_threshold: None,
+            allowed_extensions: None,
+            excluded_extensions: None,
+        }
+    }
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("store-things-test-{}-{}", label, nanos));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn extension_allowed_respects_allow_and_deny_lists() {
+        let mut config = test_config(PathBuf::new());
+        assert!(config.extension_allowed("png"));
+
+        config.allowed_extensions = Some(vec!["png".into(), "jpg".into()]);
+        assert!(config.extension_allowed("PNG"));
+        assert!(!config.extension_allowed("tmp"));
+
+        config.allowed_extensions = None;
+        config.excluded_extensions = Some(vec!["tmp".into()]);
+        assert!(!config.extension_allowed("TMP"));
+        assert!(config.extension_allowed("png"));
+    }
+
+    #[test]
+    fn snapshot_and_restore_round_trip() {
+        let source = temp_dir("source");
+        let clippings = temp_dir("clippings");
+        let dest = temp_dir("dest");
+
+        std::fs::write(source.join("a.txt"), b"hello").unwrap();
+        std::fs::create_dir(source.join("nested")).unwrap();
+        std::fs::write(source.join("nested").join("b.txt"), b"world").unwrap();
+
+        let config = test_config(clippings.clone());
+        let manifest = snapshot::snapshot(&config, &source).unwrap();
+        assert_eq!(manifest.entries.len(), 2);
+
+        snapshot::restore(&config, &manifest, &dest).unwrap();
+
+        assert_eq!(std::fs::read(dest.join("a.txt")).unwrap(), b"hello");
+        assert_eq!(
+            std::fs::read(dest.join("nested").join("b.txt")).unwrap(),
+            b"world"
+        );
+
+        std::fs::remove_dir_all(&source).unwrap();
+        std::fs::remove_dir_all(&clippings).unwrap();
+        std::fs::remove_dir_all(&dest).unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+use std::path::Path;
+
+const WIDTH: u32 = 9;
+const HEIGHT: u32 = 8;
+
+/// Computes a 64-bit dHash: downscale to 9x8 grayscale, then set bit i
+/// when pixel i is brighter than its horizontal neighbour pixel i+1.
+/// Returns `None` if `path` can't be decoded as an image.
+pub(crate) fn dhash(path: &Path) -> Option<u64> {
+    let img = image::open(path).ok()?;
+    let small = img
+        .resize_exact(WIDTH, HEIGHT, image::imageops::FilterType::Triangle)
+        .to_luma8();
+
+    let mut hash: u64 = 0;
+    let mut bit = 0;
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH - 1 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            if left > right {
+                hash |= 1 << bit;
+            }
+            bit += 1;
+        }
+    }
+    Some(hash)
+}
+
+pub(crate) fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hamming_distance_counts_differing_bits() {
+        assert_eq!(hamming_distance(0b0, 0b0), 0);
+        assert_eq!(hamming_distance(0b0, 0b1), 1);
+        assert_eq!(hamming_distance(0b1010, 0b0101), 4);
+        assert_eq!(hamming_distance(u64::MAX, 0), 64);
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::{store_blob_exact, Config};
+
+/// One file within a snapshotted tree: where it lived relative to the
+/// snapshot root, and the content digest/mode/extension needed to
+/// restore it.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ManifestEntry {
+    pub(crate) path: PathBuf,
+    pub(crate) hash: String,
+    pub(crate) mode: u32,
+    pub(crate) extension: String,
+}
+
+/// The lightweight, shareable description of a directory tree: every
+/// unique blob still lives once in the content store, this just maps
+/// relative paths back to their digests.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    pub(crate) entries: Vec<ManifestEntry>,
+}
+
+/// Recursively walks `dir`, storing every file by content hash (so
+/// identical files are stored exactly once) and recording its
+/// relative path, mode and hash in the returned manifest.
+pub(crate) fn snapshot(config: &Config, dir: &Path) -> Result<Manifest> {
+    let mut entries = Vec::new();
+    walk(config, dir, dir, &mut entries)?;
+    Ok(Manifest { entries })
+}
+
+fn walk(config: &Config, root: &Path, dir: &Path, entries: &mut Vec<ManifestEntry>) -> Result<()> {
+    for entry in std::fs::read_dir(dir).with_context(|| format!("listing {:?}", dir))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(config, root, &path, entries)?;
+        } else if path.is_file() {
+            let relative = path
+                .strip_prefix(root)
+                .context("relativizing path")?
+                .to_path_buf();
+            let mode = path
+                .metadata()
+                .context("reading file metadata")?
+                .permissions()
+                .mode();
+            let extension = path
+                .extension()
+                .map(|ext| ext.to_str().unwrap_or("").to_owned())
+                .unwrap_or_default();
+
+            let stored = store_blob_exact(config, &path)?;
+            let hash = stored
+                .file_stem()
+                .context("reading stored file name")?
+                .to_string_lossy()
+                .into_owned();
+
+            entries.push(ManifestEntry {
+                path: relative,
+                hash,
+                mode,
+                extension,
+            });
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn save_manifest(manifest: &Manifest, path: &Path) -> Result<()> {
+    let contents = serde_json::to_string_pretty(manifest).context("serializing manifest")?;
+    std::fs::write(path, contents).context("writing manifest")
+}
+
+pub(crate) fn load_manifest(path: &Path) -> Result<Manifest> {
+    let contents = std::fs::read_to_string(path).context("reading manifest")?;
+    serde_json::from_str(&contents).context("parsing manifest")
+}
+
+/// Rebuilds the tree described by `manifest` under `dest`, copying
+/// each blob back from the content store to its relative path.
+pub(crate) fn restore(config: &Config, manifest: &Manifest, dest: &Path) -> Result<()> {
+    let clippings_dir = config.get_clippings_dir()?;
+    for entry in &manifest.entries {
+        let mut blob = clippings_dir.join(&entry.hash);
+        blob.set_extension(&entry.extension);
+
+        let target = dest.join(&entry.path);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).context("creating destination directory")?;
+        }
+        std::fs::copy(&blob, &target)
+            .with_context(|| format!("restoring {:?} from {:?}", target, blob))?;
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(entry.mode))
+            .context("setting file mode")?;
+    }
+    Ok(())
+}
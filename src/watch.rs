@@ -0,0 +1,69 @@
+// Uses the `notify` 4.x API (`watcher`/`DebouncedEvent`, with
+// debouncing built in); the `notify` dependency must stay pinned to
+// `^4` since this API was removed in notify 5.
+use anyhow::{Context, Result};
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+use crate::{do_add, Config};
+
+/// How long to wait between size checks when confirming a file has
+/// stopped being written to.
+const STABILITY_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Watch `config.screenshot_dir` for new files and `do_add` them
+/// automatically, debouncing filesystem events by 500ms so partially
+/// written screenshots aren't picked up mid-write.
+pub(crate) fn run(config: &Config) -> Result<()> {
+    let screenshot_dir = config.get_screenshot_dir()?;
+
+    let (tx, rx) = channel();
+    let mut watcher = watcher(tx, Duration::from_millis(500)).context("creating watcher")?;
+    watcher
+        .watch(&screenshot_dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("watching {:?}", screenshot_dir))?;
+    log::info!("watching {:?} for new screenshots", screenshot_dir);
+
+    loop {
+        match rx.recv() {
+            Ok(DebouncedEvent::Create(path)) => {
+                if let Err(e) = handle_new_file(config, &path) {
+                    log::warn!("failed to add {:?}: {:?}", path, e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("watch error: {:?}", e);
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_new_file(config: &Config, path: &Path) -> Result<()> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    if !is_stable(path)? {
+        log::info!("{:?} still being written, not adding it", path);
+        return Ok(());
+    }
+    do_add(config, path)?;
+    Ok(())
+}
+
+/// Returns true if the file's size doesn't change across a short
+/// interval, as a best-effort signal that it's done being written.
+fn is_stable(path: &Path) -> Result<bool> {
+    let before = path.metadata().context("reading file metadata")?.len();
+    std::thread::sleep(STABILITY_CHECK_INTERVAL);
+    if !path.is_file() {
+        return Ok(false);
+    }
+    let after = path.metadata().context("reading file metadata")?.len();
+    Ok(before == after)
+}